@@ -1,77 +1,150 @@
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::env;
 use std::io;
+use std::thread;
+use std::time::Duration;
 
 use etherparse::{Ipv4HeaderSlice, TcpHeaderSlice};
+#[cfg(feature = "proto-ipv6")]
+use etherparse::Ipv6HeaderSlice;
 use tun_tap::{Iface, Mode};
 
-use crate::tcp::Quad;
+use crate::pcap::Tap;
+use crate::tcp::{IpHeaderSlice, Quad};
 
+pub mod pcap;
 pub mod tcp;
 
+/// How long to sleep between polls when no packet is waiting, matching the RFC 6298 clock
+/// granularity so retransmission timers fire promptly without busy-looping.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 fn main() -> io::Result<()> {
     let mut buf = [0u8; 1504];
     let mut connections: HashMap<tcp::Quad, tcp::Connection> = Default::default();
 
-    let mut nic = Iface::without_packet_info("tun0", Mode::Tun)
+    let iface = Iface::without_packet_info("tun0", Mode::Tun)
         .expect("Failed to initialize tun0 interface");
-    eprint!("created interface {} in {:?} mode\n", nic.name(), nic.mode());
+    let mut nic = Tap::new(iface);
+    if let Ok(path) = env::var("TCPRUST_PCAP") {
+        nic = nic.with_capture(path)?;
+    }
+    nic.set_non_blocking()?;
+    eprintln!("created interface {} in {:?} mode", nic.name(), nic.mode());
 
     loop {
-        let nbytes: usize = nic.recv(&mut buf[..])?;
-
-        match Ipv4HeaderSlice::from_slice(&buf[..nbytes]) {
-            Ok(iph) => {
-                let src = iph.source_addr();
-                let dst = iph.destination_addr();
-                if iph.protocol() != 0x06 {
-                    // not tcp
-                    continue;
+        let received = match nic.recv(&mut buf[..]) {
+            Ok(nbytes) => {
+                handle_frame(&mut nic, &mut connections, &buf[..nbytes])?;
+                true
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => false,
+            Err(e) => return Err(e),
+        };
+
+        // give every connection's retransmission timer a chance to fire
+        for (quad, conn) in connections.iter_mut() {
+            if conn.retransmission_due() {
+                if let Err(e) = conn.retransmit(&mut nic, quad) {
+                    eprintln!("retransmit error for {:?}: {}", quad, e);
                 }
+            }
+        }
+
+        // reap connections that have sat in TIME-WAIT for 2*MSL, and ones that have finished
+        // passive close (Estab -> CloseWait -> LastAck -> Closed) entirely
+        connections.retain(|_, c| !c.is_time_wait_expired() && !c.is_closed());
+
+        // only throttle when there was nothing to do - draining a backlog of frames shouldn't
+        // be capped to one segment per poll interval
+        if !received {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+fn handle_frame(
+    nic: &mut Tap,
+    connections: &mut HashMap<tcp::Quad, tcp::Connection>,
+    frame: &[u8],
+) -> io::Result<()> {
+    let nbytes = frame.len();
+    if frame.is_empty() {
+        return Ok(());
+    }
+
+    // dispatch on the IP version nibble so IPv4 and (when built with `proto-ipv6`) IPv6
+    // frames feed the same connection table
+    let iph = match frame[0] >> 4 {
+        4 => match Ipv4HeaderSlice::from_slice(frame) {
+            Ok(iph) => IpHeaderSlice::V4(iph),
+            Err(e) => {
+                eprintln!("Ipv4Header parsing error: {:?}", e);
+                return Ok(());
+            }
+        },
+        #[cfg(feature = "proto-ipv6")]
+        6 => match Ipv6HeaderSlice::from_slice(frame) {
+            Ok(iph) => IpHeaderSlice::V6(iph),
+            Err(e) => {
+                eprintln!("Ipv6Header parsing error: {:?}", e);
+                return Ok(());
+            }
+        },
+        version => {
+            eprintln!("unsupported IP version {}", version);
+            return Ok(());
+        }
+    };
+
+    if iph.protocol() != 0x06 {
+        // not tcp
+        return Ok(());
+    }
+
+    let header_len = iph.slice_len();
 
-                match TcpHeaderSlice::from_slice(&buf[iph.slice().len()..nbytes]) {
-                    Ok(tcph) => {
-                        let datai = iph.slice().len() + tcph.slice().len();
-
-                        match connections.entry(
-                            Quad {
-                                src: (src, tcph.destination_port()),
-                                dst: (dst, tcph.destination_port()),
-                            }) {
-                            Entry::Occupied(mut oe) => {
-                                if let Err(e) = oe.get_mut().on_packet(&mut nic, iph, tcph, &buf[datai..nbytes]) {
-                                    eprintln!("Error: {}", e);
-                                } else {
-                                    eprintln!("Packet {:?}", &buf[..]);
-                                }
-                            }
-
-                            Entry::Vacant(mut ve) => {
-                                if let Some(c) = tcp::Connection::default().accept(
-                                    &mut nic,
-                                    iph,
-                                    tcph,
-                                    &buf[datai..nbytes],
-                                )?
-                                {
-                                    eprintln!("Accept: {:?}", c);
-                                    ve.insert(c);
-                                }
-                            }
+    match TcpHeaderSlice::from_slice(&frame[header_len..nbytes]) {
+        Ok(tcph) => {
+            let datai = header_len + tcph.slice().len();
+            let quad = Quad::from_headers(&iph, &tcph);
+
+            match connections.entry(quad.clone()) {
+                Entry::Occupied(mut oe) => {
+                    let conn = oe.get_mut();
+                    if let Err(e) = conn.on_packet(nic, iph, tcph, &frame[datai..nbytes]) {
+                        eprintln!("Error: {}", e);
+                    } else {
+                        // drain whatever payload the connection has accepted so far and echo
+                        // it straight back, so the write half of the socket API is exercised
+                        let mut payload = [0u8; 1500];
+                        let n = conn.read(&mut payload);
+                        if n > 0 {
+                            eprintln!("read {} bytes: {:?}", n, &payload[..n]);
+                            conn.write(nic, &quad, &payload[..n])?;
                         }
                     }
-                    Err(e) => {
-                        eprintln!("TcpHeader parsing error: {:?}", e);
-                        continue;
+                }
+
+                Entry::Vacant(ve) => {
+                    if let Some(c) = tcp::Connection::default().accept(
+                        nic,
+                        iph,
+                        tcph,
+                        &frame[datai..nbytes],
+                    )?
+                    {
+                        eprintln!("Accept: {:?}", c);
+                        ve.insert(c);
                     }
                 }
             }
-            Err(e) => {
-                eprintln!("Ipv4Header parsing error: {:?}", e);
-                continue;
-            }
+        }
+        Err(e) => {
+            eprintln!("TcpHeader parsing error: {:?}", e);
         }
     }
 
-    // Ok(())
+    Ok(())
 }
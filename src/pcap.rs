@@ -0,0 +1,139 @@
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tun_tap::{Iface, Mode};
+
+/// libpcap magic number for a native-byte-order, microsecond-resolution capture file.
+const PCAP_MAGIC: u32 = 0xa1b2c3d4;
+/// LINKTYPE_RAW: no link-layer header, just a bare IPv4/IPv6 packet.
+const LINKTYPE_RAW: u32 = 101;
+/// Snapshot length advertised in the global header (the conventional maximum IPv4 packet
+/// size); frames are always written whole, never truncated to it.
+const SNAPLEN: u32 = 65535;
+
+/// Appends frames to a libpcap-format file so a capture can be opened directly in Wireshark.
+/// Modeled after smoltcp's tcpdump example: a 24-byte global header followed by one
+/// (16-byte header, payload) record per captured frame.
+struct PcapWriter {
+    file: File,
+}
+
+impl PcapWriter {
+    fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = File::create(path)?;
+        file.write_all(&PCAP_MAGIC.to_le_bytes())?;
+        file.write_all(&2u16.to_le_bytes())?; // version_major
+        file.write_all(&4u16.to_le_bytes())?; // version_minor
+        file.write_all(&0i32.to_le_bytes())?; // thiszone
+        file.write_all(&0u32.to_le_bytes())?; // sigfigs
+        file.write_all(&SNAPLEN.to_le_bytes())?;
+        file.write_all(&LINKTYPE_RAW.to_le_bytes())?;
+        Ok(PcapWriter { file })
+    }
+
+    fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let len = frame.len() as u32;
+
+        self.file.write_all(&(now.as_secs() as u32).to_le_bytes())?;
+        self.file.write_all(&now.subsec_micros().to_le_bytes())?;
+        self.file.write_all(&len.to_le_bytes())?; // incl_len
+        self.file.write_all(&len.to_le_bytes())?; // orig_len
+        self.file.write_all(frame)?;
+        Ok(())
+    }
+}
+
+/// Wraps a tun/tap `Iface` so every frame read or written can also be mirrored into a pcap
+/// capture file, for inspecting the handshake (or anything else) in Wireshark.
+pub struct Tap {
+    iface: Iface,
+    capture: Option<PcapWriter>,
+}
+
+impl Tap {
+    pub fn new(iface: Iface) -> Self {
+        Tap { iface, capture: None }
+    }
+
+    /// Start mirroring every sent/received frame into a new pcap file at `path`.
+    pub fn with_capture(mut self, path: impl AsRef<Path>) -> io::Result<Self> {
+        self.capture = Some(PcapWriter::create(path)?);
+        Ok(self)
+    }
+
+    pub fn name(&self) -> &str {
+        self.iface.name()
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.iface.mode()
+    }
+
+    pub fn set_non_blocking(&self) -> io::Result<()> {
+        self.iface.set_non_blocking()
+    }
+
+    pub fn recv(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.iface.recv(buf)?;
+        if let Some(capture) = &mut self.capture {
+            capture.write_frame(&buf[..n])?;
+        }
+        Ok(n)
+    }
+
+    pub fn send(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.iface.send(buf)?;
+        if let Some(capture) = &mut self.capture {
+            capture.write_frame(&buf[..n])?;
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+    use std::fs;
+
+    fn scratch_path(label: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("tcprust_pcap_{}_{}.pcap", label, std::process::id()))
+    }
+
+    #[test]
+    fn create_writes_a_valid_24_byte_global_header() {
+        let path = scratch_path("header");
+        PcapWriter::create(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(bytes.len(), 24);
+        assert_eq!(u32::from_le_bytes(bytes[0..4].try_into().unwrap()), PCAP_MAGIC);
+        assert_eq!(u16::from_le_bytes(bytes[4..6].try_into().unwrap()), 2); // version_major
+        assert_eq!(u16::from_le_bytes(bytes[6..8].try_into().unwrap()), 4); // version_minor
+        assert_eq!(u32::from_le_bytes(bytes[16..20].try_into().unwrap()), SNAPLEN);
+        assert_eq!(u32::from_le_bytes(bytes[20..24].try_into().unwrap()), LINKTYPE_RAW);
+    }
+
+    #[test]
+    fn write_frame_appends_a_16_byte_record_header_plus_payload() {
+        let path = scratch_path("record");
+        let mut writer = PcapWriter::create(&path).unwrap();
+        let frame = [1u8, 2, 3, 4, 5];
+        writer.write_frame(&frame).unwrap();
+        drop(writer);
+
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(bytes.len(), 24 + 16 + frame.len());
+        let record = &bytes[24..];
+        assert_eq!(u32::from_le_bytes(record[8..12].try_into().unwrap()), frame.len() as u32); // incl_len
+        assert_eq!(u32::from_le_bytes(record[12..16].try_into().unwrap()), frame.len() as u32); // orig_len
+        assert_eq!(&record[16..], &frame);
+    }
+}
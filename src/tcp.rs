@@ -1,16 +1,169 @@
 use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::hash::BuildHasher;
 use std::io;
-use std::net::Ipv4Addr;
-use std::ops::Deref;
+use std::io::Write;
+use std::net::IpAddr;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
-use etherparse::{Ipv4Header, Ipv4HeaderSlice, TcpHeader, TcpHeaderSlice};
+use etherparse::{Ipv4Header, Ipv4HeaderSlice, TcpHeader, TcpHeaderSlice, TcpOptionElement};
+#[cfg(feature = "proto-ipv6")]
+use etherparse::{Ipv6Header, Ipv6HeaderSlice};
 use etherparse::IpTrafficClass;
-use tun_tap::Iface;
+use crate::pcap::Tap;
+
+/// `etherparse::WriteError` doesn't convert into `io::Error` (the orphan rule blocks a `From`
+/// impl between two foreign types), so every header-writing call site maps it through this.
+fn write_err(e: etherparse::WriteError) -> io::Error {
+    io::Error::other(e.to_string())
+}
 
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
 pub struct Quad {
-    pub src: (Ipv4Addr, u16),
-    pub dst: (Ipv4Addr, u16),
+    pub src: (IpAddr, u16),
+    pub dst: (IpAddr, u16),
+}
+
+/// Borrowed view over an IPv4 or IPv6 header, letting `main.rs` dispatch on the version
+/// nibble once and feed either kind into the same connection table. Mirrors smoltcp's
+/// `proto-ipv4`/`proto-ipv6` Cargo feature split (declared in `Cargo.toml`): an IPv4-only
+/// build never instantiates, or even compiles, the `V6` arm.
+#[derive(Clone, Debug)]
+pub enum IpHeaderSlice<'a> {
+    V4(Ipv4HeaderSlice<'a>),
+    #[cfg(feature = "proto-ipv6")]
+    V6(Ipv6HeaderSlice<'a>),
+}
+
+impl<'a> IpHeaderSlice<'a> {
+    pub fn source_addr(&self) -> IpAddr {
+        match self {
+            IpHeaderSlice::V4(iph) => IpAddr::V4(iph.source_addr()),
+            #[cfg(feature = "proto-ipv6")]
+            IpHeaderSlice::V6(iph) => IpAddr::V6(iph.source_addr()),
+        }
+    }
+
+    pub fn destination_addr(&self) -> IpAddr {
+        match self {
+            IpHeaderSlice::V4(iph) => IpAddr::V4(iph.destination_addr()),
+            #[cfg(feature = "proto-ipv6")]
+            IpHeaderSlice::V6(iph) => IpAddr::V6(iph.destination_addr()),
+        }
+    }
+
+    /// Upper-layer protocol number: IPv4's `protocol` field or IPv6's `next_header` field.
+    /// `6` is TCP either way.
+    pub fn protocol(&self) -> u8 {
+        match self {
+            IpHeaderSlice::V4(iph) => iph.protocol(),
+            #[cfg(feature = "proto-ipv6")]
+            IpHeaderSlice::V6(iph) => iph.next_header(),
+        }
+    }
+
+    /// Length of the header itself, i.e. the offset where the TCP segment starts in the frame.
+    pub fn slice_len(&self) -> usize {
+        match self {
+            IpHeaderSlice::V4(iph) => iph.slice().len(),
+            #[cfg(feature = "proto-ipv6")]
+            IpHeaderSlice::V6(iph) => iph.slice().len(),
+        }
+    }
+
+    /// Compute the TCP checksum over this header's pseudo-header plus `payload`, for the caller
+    /// to compare against the segment's checksum field; `None` if it cannot be computed.
+    fn calc_tcp_checksum(&self, tcph: &TcpHeaderSlice, payload: &[u8]) -> Option<u16> {
+        match self {
+            IpHeaderSlice::V4(iph) => tcph.calc_checksum_ipv4(iph, payload).ok(),
+            #[cfg(feature = "proto-ipv6")]
+            IpHeaderSlice::V6(iph) => tcph.calc_checksum_ipv6(iph, payload).ok(),
+        }
+    }
+}
+
+/// An owned IPv4 or IPv6 header for a connection's outgoing segments, built fresh for each
+/// segment the same way the IPv4-only code used to build an `Ipv4Header` inline. Which arm is
+/// constructed follows the address family of the endpoints it is built from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum IpRepr {
+    V4(Ipv4Header),
+    #[cfg(feature = "proto-ipv6")]
+    V6(Ipv6Header),
+}
+
+impl Default for IpRepr {
+    fn default() -> Self {
+        IpRepr::V4(Ipv4Header::default())
+    }
+}
+
+impl IpRepr {
+    /// Build a zero-payload-length header addressed from `src` to `dst`, carrying TCP.
+    fn new(src: IpAddr, dst: IpAddr, time_to_live: u8) -> Self {
+        match (src, dst) {
+            (IpAddr::V4(src), IpAddr::V4(dst)) => IpRepr::V4(Ipv4Header::new(
+                0,
+                time_to_live,
+                IpTrafficClass::Tcp,
+                src.octets(),
+                dst.octets(),
+            )),
+            #[cfg(feature = "proto-ipv6")]
+            (IpAddr::V6(src), IpAddr::V6(dst)) => IpRepr::V6(Ipv6Header {
+                traffic_class: 0,
+                flow_label: 0,
+                payload_length: 0,
+                next_header: IpTrafficClass::Tcp as u8,
+                hop_limit: time_to_live,
+                source: src.octets(),
+                destination: dst.octets(),
+            }),
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("a Quad's two endpoints are always the same IP version"),
+        }
+    }
+
+    /// Set the payload length field now that the TCP header + data has been framed.
+    fn set_payload_len(&mut self, len: usize) {
+        match self {
+            IpRepr::V4(ip) => {
+                let _ = ip.set_payload_len(len);
+            }
+            #[cfg(feature = "proto-ipv6")]
+            IpRepr::V6(ip) => {
+                let _ = ip.set_payload_length(len);
+            }
+        }
+    }
+
+    /// Fill in the header checksum (a no-op for IPv6, which has none).
+    fn fill_header_checksum(&mut self) {
+        match self {
+            IpRepr::V4(ip) => ip.header_checksum = ip.calc_header_checksum().unwrap_or(0),
+            #[cfg(feature = "proto-ipv6")]
+            IpRepr::V6(_) => {}
+        }
+    }
+
+    /// Compute the TCP checksum over this header's pseudo-header plus `payload`.
+    fn calc_tcp_checksum(&self, tcph: &TcpHeader, payload: &[u8]) -> u16 {
+        match self {
+            IpRepr::V4(ip) => tcph.calc_checksum_ipv4(ip, payload).unwrap_or(0),
+            #[cfg(feature = "proto-ipv6")]
+            IpRepr::V6(ip) => tcph.calc_checksum_ipv6(ip, payload).unwrap_or(0),
+        }
+    }
+
+    fn write(&self, writer: &mut impl Write) -> Result<(), etherparse::WriteError> {
+        match self {
+            IpRepr::V4(ip) => ip.write(writer),
+            #[cfg(feature = "proto-ipv6")]
+            IpRepr::V6(ip) => ip.write(writer),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
@@ -22,6 +175,10 @@ pub enum State {
     SynRcvd,
     FinWait1,
     FinWait2,
+    Closing,
+    CloseWait,
+    LastAck,
+    TimeWait,
 }
 
 /// Send Sequence Space (RFC 793  S3.2 F4)
@@ -53,6 +210,35 @@ struct SendSequenceSpace {
     iss: u32,
 }
 
+impl SendSequenceSpace {
+    /// Generate an initial sequence number per RFC 6528: `ISS = M + F(src_ip, src_port, dst_ip,
+    /// dst_port)`. `M` is a timer that increments roughly every 4 microseconds, and `F` is a
+    /// keyed hash of the four-tuple using a secret generated once per process. This keeps
+    /// successive connections on the same tuple from reusing a sequence space and makes the
+    /// ISN unpredictable to an off-path attacker.
+    fn generate_isn(quad: &Quad) -> u32 {
+        /// per-process secret key for `F`, generated once from the OS RNG via `RandomState`
+        fn secret() -> &'static RandomState {
+            static SECRET: OnceLock<RandomState> = OnceLock::new();
+            SECRET.get_or_init(RandomState::new)
+        }
+
+        /// reference point for `M`, taken the first time an ISN is generated, so it's monotonic
+        /// even across a wall-clock jump (NTP correction, manual clock change) - unlike `SystemTime`, `Instant` can only
+        /// move forward.
+        fn start() -> &'static Instant {
+            static START: OnceLock<Instant> = OnceLock::new();
+            START.get_or_init(Instant::now)
+        }
+
+        let m = start().elapsed().as_nanos() / 4000;
+
+        let f = secret().hash_one(quad) as u32;
+
+        (m as u32).wrapping_add(f)
+    }
+}
+
 
 /// Receive Sequence Space (RFC 793  S3.2 F5)
 ///
@@ -77,6 +263,150 @@ struct RecvSequenceSpace {
 }
 
 
+/// Default MSS used when the peer does not advertise one via the MSS option.
+const DEFAULT_MSS: usize = 536;
+
+/// Pull the peer's advertised MSS, if any, out of a SYN's TCP options.
+fn peer_mss(tcph: &TcpHeaderSlice) -> Option<usize> {
+    tcph.options_iterator().find_map(|opt| match opt {
+        Ok(TcpOptionElement::MaximumSegmentSize(mss)) => Some(mss as usize),
+        _ => None,
+    })
+}
+
+/// Maximum Segment Lifetime (RFC 793  S3.3); TIME-WAIT holds the connection for 2*MSL.
+const MSL: Duration = Duration::from_secs(120);
+
+/// Clock granularity `G` used in the RTO computation (RFC 6298 S2).
+const CLOCK_GRANULARITY: Duration = Duration::from_millis(100);
+
+/// Lower bound on the RTO (RFC 6298 S2.4): never retransmit sooner than this.
+const MIN_RTO: Duration = Duration::from_secs(1);
+
+/// One segment we've sent and are waiting to have acknowledged.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct SentSegment {
+    /// sequence number the segment started at
+    seq: u32,
+    /// number of sequence numbers it consumed (payload length, or 1 for a bare SYN/FIN)
+    len: u32,
+    /// whether this segment carried the SYN bit (set only for a listener's SYN-ACK)
+    syn: bool,
+    /// whether this segment carried the FIN bit
+    fin: bool,
+    /// when it was (last) sent
+    sent_at: Instant,
+    /// set once retransmitted, so Karn's algorithm excludes it from RTT sampling
+    retransmitted: bool,
+}
+
+/// Per-connection round-trip estimator and retransmission queue, combining Jacobson's RTT
+/// estimator with Karn's algorithm (RFC 6298).
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct RetransmissionTimer {
+    srtt: Option<Duration>,
+    rttvar: Duration,
+    rto: Duration,
+    queue: VecDeque<SentSegment>,
+}
+
+impl Default for RetransmissionTimer {
+    fn default() -> Self {
+        RetransmissionTimer {
+            srtt: None,
+            rttvar: Duration::from_secs(0),
+            rto: MIN_RTO,
+            queue: Default::default(),
+        }
+    }
+}
+
+impl RetransmissionTimer {
+    /// Record that we just (re)sent `len` sequence numbers' worth of data starting at `seq`.
+    fn on_send(&mut self, seq: u32, len: u32, syn: bool, fin: bool) {
+        self.queue.push_back(SentSegment {
+            seq,
+            len,
+            syn,
+            fin,
+            sent_at: Instant::now(),
+            retransmitted: false,
+        });
+    }
+
+    /// Retire every fully-acknowledged segment now that `send.una` has advanced to `una`,
+    /// taking one RTT sample from the oldest retired segment that was never retransmitted.
+    fn on_ack(&mut self, una: u32) {
+        let mut sampled = false;
+        while let Some(seg) = self.queue.front() {
+            if una.wrapping_sub(seg.seq) < seg.len.max(1) {
+                break;
+            }
+            let seg = self.queue.pop_front().unwrap();
+            if !sampled && !seg.retransmitted {
+                self.sample(seg.sent_at.elapsed());
+                sampled = true;
+            }
+        }
+    }
+
+    /// Jacobson's RTT estimator (RFC 6298 S2): update `SRTT`/`RTTVAR` from a fresh sample `r`
+    /// and derive the RTO from them, floored at `MIN_RTO`.
+    fn sample(&mut self, r: Duration) {
+        match self.srtt {
+            None => {
+                self.srtt = Some(r);
+                self.rttvar = r / 2;
+            }
+            Some(srtt) => {
+                let delta = srtt.abs_diff(r);
+                self.rttvar = self.rttvar - self.rttvar / 4 + delta / 4;
+                self.srtt = Some(srtt - srtt / 8 + r / 8);
+            }
+        }
+        let srtt = self.srtt.unwrap();
+        self.rto = (srtt + (CLOCK_GRANULARITY.max(self.rttvar * 4))).max(MIN_RTO);
+    }
+
+    /// Whether the oldest outstanding segment has been unacknowledged longer than the RTO.
+    fn expired(&self) -> bool {
+        self.queue
+            .front()
+            .is_some_and(|seg| seg.sent_at.elapsed() >= self.rto)
+    }
+
+    fn oldest(&self) -> Option<&SentSegment> {
+        self.queue.front()
+    }
+
+    /// Karn's algorithm: back off the RTO exponentially and mark the oldest segment as
+    /// retransmitted (excluding it from future RTT sampling) with a fresh send time.
+    fn on_retransmit(&mut self) {
+        self.rto *= 2;
+        if let Some(seg) = self.queue.front_mut() {
+            seg.retransmitted = true;
+            seg.sent_at = Instant::now();
+        }
+    }
+}
+
+/// Per-direction toggle for checksum computation/verification, modeled after smoltcp's
+/// `ChecksumCapabilities`. Both directions default to enabled; a user whose NIC offloads
+/// checksums can disable the corresponding side to skip redundant work.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChecksumCaps {
+    /// compute and fill in the IPv4/TCP checksum on outgoing segments
+    pub tx: bool,
+    /// verify the TCP checksum on incoming segments, dropping the segment on mismatch
+    pub rx: bool,
+}
+
+impl Default for ChecksumCaps {
+    fn default() -> Self {
+        ChecksumCaps { tx: true, rx: true }
+    }
+}
+
 /// # Connection States
 /// * TCP control bits (from left to right)
 /// * URG: Urgent Pointer field significant
@@ -90,17 +420,30 @@ pub struct Connection {
     state: State,
     send: SendSequenceSpace,
     recv: RecvSequenceSpace,
-    ip: Ipv4Header,
+    ip: IpRepr,
+    checksum: ChecksumCaps,
+
+    /// bytes received from the peer and not yet read by user code
+    incoming: VecDeque<u8>,
+    /// bytes handed to us by user code and not yet acknowledged by the peer
+    unacked: VecDeque<u8>,
+
+    /// sequence number of our outstanding FIN, cleared once it's been acked
+    fin_seq: Option<u32>,
+    /// when we entered TIME-WAIT; the connection is reaped 2*MSL after this
+    time_wait_since: Option<Instant>,
+
+    /// retransmission queue and Jacobson/Karn RTO estimate
+    rto: RetransmissionTimer,
+    /// congestion window in bytes, halved on each retransmit but never below `DEFAULT_MSS`
+    cwnd: usize,
 }
 
 
 impl Quad {
     /// create a TCP connection 'Quad' from
     /// the headers of a tcp/ip packet
-    pub fn from_headers<'a>(
-        iph: &etherparse::Ipv4HeaderSlice<'a>,
-        tcph: &etherparse::TcpHeaderSlice<'a>,
-    ) -> Self {
+    pub fn from_headers<'a>(iph: &IpHeaderSlice<'a>, tcph: &etherparse::TcpHeaderSlice<'a>) -> Self {
         Self {
             src: (iph.source_addr(), tcph.source_port()),
             dst: (iph.destination_addr(), tcph.destination_port()),
@@ -128,7 +471,14 @@ impl Default for Connection {
                 up: false,
                 irs: 0,
             },
-            ip: Ipv4Header::default(),
+            ip: IpRepr::default(),
+            checksum: ChecksumCaps::default(),
+            incoming: Default::default(),
+            unacked: Default::default(),
+            fin_seq: None,
+            time_wait_since: None,
+            rto: RetransmissionTimer::default(),
+            cwnd: DEFAULT_MSS,
         }
     }
 }
@@ -137,8 +487,8 @@ impl Default for Connection {
 impl Connection {
     pub fn accept<'a>(
         &mut self,
-        nic: &'a mut Iface,
-        iph: Ipv4HeaderSlice<'a>,
+        nic: &'a mut Tap,
+        iph: IpHeaderSlice<'a>,
         tcph: TcpHeaderSlice<'a>,
         data: &'a [u8],
     ) -> io::Result<Option<Self>> {
@@ -148,6 +498,15 @@ impl Connection {
             return Ok(None);
         }
 
+        if self.checksum.rx {
+            let expected = tcph.checksum();
+            let computed = iph.calc_tcp_checksum(&tcph, data).unwrap_or(expected);
+            if computed != expected {
+                eprintln!("dropping SYN with bad TCP checksum");
+                return Ok(None);
+            }
+        }
+
         // log that we've recieved a packet while listening
         println!("establishing connection {}:{} â†’ {}:{}", iph.source_addr(), tcph.source_port(), iph.destination_addr(), tcph.destination_port());
 
@@ -156,29 +515,25 @@ impl Connection {
         self.recv.irs = tcph.sequence_number();
         self.recv.wnd = tcph.window_size();
 
-        // start establishing a response
+        // start establishing a response, bounding our congestion window by whatever MSS the
+        // peer advertised (falling back to the conservative default if it didn't)
+        let cwnd = peer_mss(&tcph).unwrap_or(DEFAULT_MSS);
+        let iss = SendSequenceSpace::generate_isn(&Quad::from_headers(&iph, &tcph));
         let mut syn_ack = TcpHeader::new(
             tcph.destination_port(),
             tcph.source_port(),
-            0,
+            iss,
             10,
         );
 
-        let mut ip = Ipv4Header::new(
-            syn_ack.header_len(),
-            64,
-            IpTrafficClass::Tcp,
-            iph.destination_addr().octets(),
-            iph.source_addr().octets(),
-        );
+        let mut ip = IpRepr::new(iph.destination_addr(), iph.source_addr(), 64);
 
-        let iss: u32 = 0;
         let mut c = Connection {
             state: State::SynRcvd,
             send: SendSequenceSpace {
                 iss,
-                una: self.send.iss,
-                nxt: self.send.una + 1,
+                una: iss,
+                nxt: iss + 1,
                 wnd: 10,
                 up: false,
                 wl1: 0,
@@ -190,35 +545,92 @@ impl Connection {
                 wnd: tcph.window_size(),
                 up: false,
             },
-            ip: Ipv4Header::default(),
+            ip: IpRepr::default(),
+            checksum: self.checksum.clone(),
+            incoming: Default::default(),
+            unacked: Default::default(),
+            fin_seq: None,
+            time_wait_since: None,
+            rto: RetransmissionTimer::default(),
+            cwnd,
         };
 
         syn_ack.acknowledgment_number = self.recv.nxt;
         syn_ack.syn = true;
         syn_ack.ack = true;
-        c.ip.set_payload_len(syn_ack.header_len() as usize + 0); // 0 is len of data
+        ip.set_payload_len(syn_ack.header_len() as usize);
+        c.ip = ip.clone();
 
-        let mut unwritten = &mut buf[..];
-        let mut written = 0;
+        if self.checksum.tx {
+            ip.fill_header_checksum();
+            syn_ack.checksum = ip.calc_tcp_checksum(&syn_ack, &[]);
+        }
 
-        let mut unwritten = {
+        let unwritten = {
             let mut unwritten = &mut buf[..];
-            ip.write(&mut unwritten);
-            syn_ack.write(&mut unwritten);
+            ip.write(&mut unwritten).map_err(write_err)?;
+            syn_ack.write(&mut unwritten)?;
             unwritten.len()
         };
 
-        nic.send(&buf[..unwritten]);
+        nic.send(&buf[..buf.len() - unwritten])?;
+        // so a dropped SYN-ACK gets resent rather than lost forever
+        c.rto.on_send(iss, 1, true, false);
         Ok(Some(c))
     }
 
+    /// Override which directions compute/verify checksums. Defaults to both enabled; disable
+    /// a side when the NIC already offloads that checksum.
+    pub fn set_checksum_caps(&mut self, caps: ChecksumCaps) {
+        self.checksum = caps;
+    }
+
+    /// User-initiated close: send a FIN and begin active teardown. A no-op outside `Estab`
+    /// (active close) and `CloseWait` (closing after the peer already sent their FIN).
+    pub fn close(&mut self, nic: &mut Tap, quad: &Quad) -> io::Result<()> {
+        match self.state {
+            State::Estab => {
+                self.send_fin(nic, quad)?;
+                self.state = State::FinWait1;
+            }
+            State::CloseWait => {
+                self.send_fin(nic, quad)?;
+                self.state = State::LastAck;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Whether this connection has sat in TIME-WAIT for 2*MSL and can be reaped from the
+    /// connection table.
+    pub fn is_time_wait_expired(&self) -> bool {
+        self.state == State::TimeWait
+            && self.time_wait_since.is_some_and(|since| since.elapsed() >= MSL * 2)
+    }
+
+    /// Whether this connection has reached `Closed` (the final state of passive close) and can
+    /// be reaped from the connection table immediately, without waiting out TIME-WAIT.
+    pub fn is_closed(&self) -> bool {
+        self.state == State::Closed
+    }
+
     pub fn on_packet<'a>(
         &mut self,
-        nic: &mut Iface,
-        iph: Ipv4HeaderSlice<'a>,
+        nic: &mut Tap,
+        iph: IpHeaderSlice<'a>,
         tcph: TcpHeaderSlice<'a>,
         data: &'a [u8],
     ) -> io::Result<()> {
+        if self.checksum.rx {
+            let expected = tcph.checksum();
+            let computed = iph.calc_tcp_checksum(&tcph, data).unwrap_or(expected);
+            if computed != expected {
+                eprintln!("dropping segment with bad TCP checksum");
+                return Ok(());
+            }
+        }
+
         // acceptable ack check - https://tools.ietf.org/html/rfc793#section-3.3
         // SND.UNA < SEG.ACK =< SND.NXT - is violated if n is between u and a
         let ackn = tcph.acknowledgment_number();
@@ -228,7 +640,7 @@ impl Connection {
 
         // valid segment checks
         // RCV.NXT =< SEG.SEQ < RCV.NXT+RCV.WND
-        let datalen = data.deref().len() as u32;
+        let datalen = data.len() as u32;
         let seqn = tcph.sequence_number();
         let wend = self.recv.nxt.wrapping_add(self.recv.wnd as u32);
 
@@ -238,14 +650,75 @@ impl Connection {
             return Ok(());
         }
 
+        // the ack is acceptable for our send space - retire the bytes it covers and let the
+        // peer's advertised window shrink/grow our notion of how much room we have to send into
+        let acked = ackn.wrapping_sub(self.send.una) as usize;
+        if acked > 0 {
+            self.unacked.drain(..acked.min(self.unacked.len()));
+            self.send.una = ackn;
+            self.rto.on_ack(self.send.una);
+        }
+        self.send.wnd = tcph.window_size();
+
+        let quad = Quad::from_headers(&iph, &tcph);
+
+        // did this ack cover our outstanding FIN?
+        if let Some(fin_seq) = self.fin_seq {
+            if self.send.una == fin_seq.wrapping_add(1) {
+                self.fin_seq = None;
+                self.state = match self.state.clone() {
+                    State::FinWait1 => State::FinWait2,
+                    State::Closing => {
+                        self.time_wait_since = Some(Instant::now());
+                        State::TimeWait
+                    }
+                    State::LastAck => State::Closed,
+                    other => other,
+                };
+            }
+        }
+
         // next, valid segment check
         match self.state {
-            State::SynRcvd => {
-                // we expect to get an ACK for our SYN so we can transition to established state
+            // we expect to get an ACK for our SYN so we can transition to established state
+            State::SynRcvd if tcph.ack() => {
+                self.state = State::Estab;
             }
 
             State::Estab => {
-                unimplemented!();
+                if !data.is_empty() {
+                    self.incoming.extend(data.iter());
+                    self.recv.nxt = self.recv.nxt.wrapping_add(datalen);
+                    self.recv.wnd = self.recv.wnd.saturating_sub(datalen as u16);
+                }
+
+                if tcph.fin() {
+                    self.recv.nxt = self.recv.nxt.wrapping_add(1);
+                    self.state = State::CloseWait;
+                }
+
+                // send whatever of our write buffer the peer's window and MSS allow, and ack
+                // whatever of theirs we just accepted (including the FIN, if any)
+                self.send_from_unacked(nic, &quad)?;
+            }
+
+            // simultaneous close: the peer's FIN arrived before ours was acked
+            State::FinWait1 if tcph.fin() => {
+                self.recv.nxt = self.recv.nxt.wrapping_add(1);
+                self.state = State::Closing;
+                self.send_from_unacked(nic, &quad)?;
+            }
+
+            State::FinWait2 if tcph.fin() => {
+                self.recv.nxt = self.recv.nxt.wrapping_add(1);
+                self.time_wait_since = Some(Instant::now());
+                self.state = State::TimeWait;
+                self.send_from_unacked(nic, &quad)?;
+            }
+
+            State::Closing | State::CloseWait | State::LastAck | State::TimeWait => {
+                // retransmitted FIN or stray segment after our side has already reacted; the
+                // generic ack handling above already took care of anything relevant
             }
 
             _ => {}
@@ -253,6 +726,162 @@ impl Connection {
 
         Ok(())
     }
+
+    /// Copy up to `buf.len()` bytes of received, unread payload into `buf`, returning the
+    /// number of bytes copied. Mirrors `std::io::Read::read` without requiring the trait.
+    ///
+    /// Draining `incoming` frees up room in our receive buffer, so re-open the advertised
+    /// window by the same amount - otherwise `recv.wnd` only ever shrinks and eventually pins
+    /// at 0, starving the connection even once the application has caught up on reading.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let n = buf.len().min(self.incoming.len());
+        for (slot, byte) in buf.iter_mut().zip(self.incoming.drain(..n)) {
+            *slot = byte;
+        }
+        self.recv.wnd = self.recv.wnd.saturating_add(n as u16);
+        n
+    }
+
+    /// Queue up to `buf.len()` bytes for transmission, returning the number of bytes accepted.
+    /// Queued bytes are framed into segments and sent as window and peer MSS allow.
+    pub fn write(&mut self, nic: &mut Tap, quad: &Quad, buf: &[u8]) -> io::Result<usize> {
+        self.unacked.extend(buf.iter());
+        self.send_from_unacked(nic, quad)?;
+        Ok(buf.len())
+    }
+
+    /// Frame and transmit as much of `unacked` as `send.wnd`, the congestion window, and the
+    /// peer MSS allow, piggy-backing an ACK of whatever we've accepted from the peer so far.
+    fn send_from_unacked(&mut self, nic: &mut Tap, quad: &Quad) -> io::Result<()> {
+        let mut buf = [0u8; 1500];
+
+        let inflight = self.send.nxt.wrapping_sub(self.send.una) as usize;
+        let not_yet_sent = self.unacked.len().saturating_sub(inflight);
+        let window = (self.send.wnd as usize).saturating_sub(inflight);
+        let nsend = window.min(self.cwnd).min(not_yet_sent);
+
+        let mut ack = TcpHeader::new(quad.dst.1, quad.src.1, self.send.nxt, self.send.wnd);
+        ack.acknowledgment_number = self.recv.nxt;
+        ack.ack = true;
+
+        let mut ip = IpRepr::new(quad.dst.0, quad.src.0, 64);
+
+        let seq = self.send.nxt;
+        let payload: Vec<u8> = self
+            .unacked
+            .iter()
+            .skip(inflight)
+            .take(nsend)
+            .copied()
+            .collect();
+        ip.set_payload_len(ack.header_len() as usize + payload.len());
+
+        if self.checksum.tx {
+            ip.fill_header_checksum();
+            ack.checksum = ip.calc_tcp_checksum(&ack, &payload);
+        }
+
+        let unwritten = {
+            let mut unwritten = &mut buf[..];
+            ip.write(&mut unwritten).map_err(write_err)?;
+            ack.write(&mut unwritten)?;
+            unwritten.write_all(&payload)?;
+            unwritten.len()
+        };
+
+        if !payload.is_empty() {
+            self.rto.on_send(seq, payload.len() as u32, false, false);
+        }
+        self.send.nxt = self.send.nxt.wrapping_add(payload.len() as u32);
+
+        nic.send(&buf[..buf.len() - unwritten])?;
+        Ok(())
+    }
+
+    /// Send a bare FIN/ACK, consuming one sequence number, and remember it in `fin_seq` so the
+    /// generic ack-handling in `on_packet` can notice when the peer finally acks it.
+    fn send_fin(&mut self, nic: &mut Tap, quad: &Quad) -> io::Result<()> {
+        let seq = self.send.nxt;
+        self.transmit_segment(nic, quad, seq, &[], false, true)?;
+        self.fin_seq = Some(seq);
+        self.rto.on_send(seq, 1, false, true);
+        self.send.nxt = self.send.nxt.wrapping_add(1);
+        Ok(())
+    }
+
+    /// Low-level framing/sending of a single segment carrying `payload` starting at `seq`,
+    /// optionally with the SYN and/or FIN bits set. Does not touch `send.nxt`, `fin_seq`, or the
+    /// RTO queue - callers that consume new sequence space handle that themselves, so this can
+    /// also be used to retransmit a segment byte-for-byte.
+    fn transmit_segment(
+        &mut self,
+        nic: &mut Tap,
+        quad: &Quad,
+        seq: u32,
+        payload: &[u8],
+        syn: bool,
+        fin: bool,
+    ) -> io::Result<()> {
+        let mut buf = [0u8; 1500];
+
+        let mut tcph = TcpHeader::new(quad.dst.1, quad.src.1, seq, self.send.wnd);
+        tcph.acknowledgment_number = self.recv.nxt;
+        tcph.ack = true;
+        tcph.syn = syn;
+        tcph.fin = fin;
+
+        let mut ip = IpRepr::new(quad.dst.0, quad.src.0, 64);
+        ip.set_payload_len(tcph.header_len() as usize + payload.len());
+
+        if self.checksum.tx {
+            ip.fill_header_checksum();
+            tcph.checksum = ip.calc_tcp_checksum(&tcph, payload);
+        }
+
+        let unwritten = {
+            let mut unwritten = &mut buf[..];
+            ip.write(&mut unwritten).map_err(write_err)?;
+            tcph.write(&mut unwritten)?;
+            unwritten.write_all(payload)?;
+            unwritten.len()
+        };
+
+        nic.send(&buf[..buf.len() - unwritten])?;
+        Ok(())
+    }
+
+    /// Whether the oldest outstanding segment has sat unacknowledged past the current RTO and
+    /// is due for retransmission.
+    pub fn retransmission_due(&self) -> bool {
+        self.rto.expired()
+    }
+
+    /// Resend the oldest unacknowledged segment (per Karn's algorithm, without taking an RTT
+    /// sample from it), back off the RTO exponentially, and shrink the congestion window.
+    pub fn retransmit(&mut self, nic: &mut Tap, quad: &Quad) -> io::Result<()> {
+        let Some(seg) = self.rto.oldest().cloned() else {
+            return Ok(());
+        };
+
+        if seg.syn {
+            // a lost SYN-ACK: resend bare, exactly as `accept` originally sent it
+            self.transmit_segment(nic, quad, seg.seq, &[], true, false)?;
+        } else if seg.fin {
+            self.transmit_segment(nic, quad, seg.seq, &[], false, true)?;
+        } else {
+            let payload: Vec<u8> = self
+                .unacked
+                .iter()
+                .take(seg.len as usize)
+                .copied()
+                .collect();
+            self.transmit_segment(nic, quad, seg.seq, &payload, false, false)?;
+        }
+
+        self.rto.on_retransmit();
+        self.cwnd = (self.cwnd / 2).max(DEFAULT_MSS);
+        Ok(())
+    }
 }
 
 // TODO - review this
@@ -278,3 +907,178 @@ fn is_between_wrapped(start: u32, x: u32, end: u32) -> bool {
     }
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peer_mss_parses_the_mss_option() {
+        let mut tcph = TcpHeader::new(1234, 80, 0, 0);
+        tcph.set_options(&[TcpOptionElement::MaximumSegmentSize(1460)]).unwrap();
+
+        let mut buf = [0u8; 64];
+        let unwritten = {
+            let mut unwritten = &mut buf[..];
+            tcph.write(&mut unwritten).unwrap();
+            unwritten.len()
+        };
+        let parsed = TcpHeaderSlice::from_slice(&buf[..buf.len() - unwritten]).unwrap();
+
+        assert_eq!(peer_mss(&parsed), Some(1460));
+    }
+
+    #[test]
+    fn peer_mss_is_none_without_the_option() {
+        let tcph = TcpHeader::new(1234, 80, 0, 0);
+
+        let mut buf = [0u8; 64];
+        let unwritten = {
+            let mut unwritten = &mut buf[..];
+            tcph.write(&mut unwritten).unwrap();
+            unwritten.len()
+        };
+        let parsed = TcpHeaderSlice::from_slice(&buf[..buf.len() - unwritten]).unwrap();
+
+        assert_eq!(peer_mss(&parsed), None);
+    }
+
+    #[test]
+    fn is_between_wrapped_without_sequence_wraparound() {
+        assert!(is_between_wrapped(0, 5, 10));
+        assert!(!is_between_wrapped(0, 15, 10));
+    }
+
+    #[test]
+    fn is_between_wrapped_across_sequence_wraparound() {
+        // forward path from `start` crosses u32::MAX and wraps to 0, 1, ... before `end`
+        assert!(is_between_wrapped(u32::MAX - 2, u32::MAX - 1, 1));
+        // `x` lies just past `end`, i.e. outside the wrapped range
+        assert!(!is_between_wrapped(u32::MAX - 2, 5, 1));
+    }
+
+    #[test]
+    fn sample_applies_jacobson_karn_formula() {
+        let mut rto = RetransmissionTimer::default();
+
+        rto.sample(Duration::from_millis(200));
+        assert_eq!(rto.srtt, Some(Duration::from_millis(200)));
+        assert_eq!(rto.rttvar, Duration::from_millis(100));
+
+        rto.sample(Duration::from_millis(400));
+        // srtt' = srtt - srtt/8 + r/8 = 200ms - 25ms + 50ms
+        assert_eq!(rto.srtt, Some(Duration::from_millis(225)));
+        // delta = |200ms - 400ms|; rttvar' = rttvar - rttvar/4 + delta/4 = 100ms - 25ms + 50ms
+        assert_eq!(rto.rttvar, Duration::from_millis(125));
+    }
+
+    #[test]
+    fn on_ack_retires_fully_acked_segments_and_samples_rtt() {
+        let mut rto = RetransmissionTimer::default();
+        rto.on_send(100, 10, false, false);
+
+        rto.on_ack(110);
+
+        assert!(rto.oldest().is_none());
+        assert!(rto.srtt.is_some());
+    }
+
+    #[test]
+    fn on_retransmit_doubles_rto_and_marks_segment_retransmitted() {
+        let mut rto = RetransmissionTimer::default();
+        rto.on_send(0, 1, true, false);
+        let rto_before = rto.rto;
+
+        rto.on_retransmit();
+
+        assert_eq!(rto.rto, rto_before * 2);
+        assert!(rto.oldest().unwrap().retransmitted);
+    }
+
+    #[test]
+    fn expired_is_false_immediately_after_send() {
+        let mut rto = RetransmissionTimer::default();
+        assert!(!rto.expired());
+
+        rto.on_send(0, 1, false, true);
+        assert!(!rto.expired());
+    }
+
+    #[test]
+    fn read_reopens_the_advertised_window() {
+        let mut conn = Connection::default();
+        conn.recv.wnd = 0;
+        conn.incoming.extend([1u8, 2, 3, 4]);
+
+        let mut buf = [0u8; 2];
+        let n = conn.read(&mut buf);
+
+        assert_eq!(n, 2);
+        assert_eq!(&buf, &[1, 2]);
+        assert_eq!(conn.recv.wnd, 2);
+    }
+
+    #[test]
+    fn closed_connections_are_reapable() {
+        let mut conn = Connection::default();
+        assert!(!conn.is_closed());
+
+        conn.state = State::Closed;
+        assert!(conn.is_closed());
+        assert!(!conn.is_time_wait_expired(), "Closed is reaped on its own signal, not via TIME-WAIT");
+    }
+
+    #[test]
+    fn fill_header_checksum_sets_the_computed_ipv4_checksum() {
+        let src = IpAddr::V4(std::net::Ipv4Addr::new(192, 168, 1, 1));
+        let dst = IpAddr::V4(std::net::Ipv4Addr::new(192, 168, 1, 2));
+        let mut ip = IpRepr::new(src, dst, 64);
+
+        ip.fill_header_checksum();
+
+        match ip {
+            IpRepr::V4(inner) => {
+                assert_eq!(inner.header_checksum, inner.calc_header_checksum().unwrap())
+            }
+            #[cfg(feature = "proto-ipv6")]
+            IpRepr::V6(_) => unreachable!("constructed as V4"),
+        }
+    }
+
+    #[test]
+    fn ip_repr_checksum_matches_the_borrowed_verification_path() {
+        let src = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1));
+        let dst = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 2));
+        let payload = b"hello";
+
+        let mut tcph = TcpHeader::new(1234, 80, 100, 4096);
+        tcph.ack = true;
+        tcph.acknowledgment_number = 1;
+
+        let mut ip = IpRepr::new(src, dst, 64);
+        ip.set_payload_len(tcph.header_len() as usize + payload.len());
+        ip.fill_header_checksum();
+        tcph.checksum = ip.calc_tcp_checksum(&tcph, payload);
+
+        let mut buf = [0u8; 1500];
+        let unwritten = {
+            let mut unwritten = &mut buf[..];
+            ip.write(&mut unwritten).unwrap();
+            tcph.write(&mut unwritten).unwrap();
+            unwritten.write_all(payload).unwrap();
+            unwritten.len()
+        };
+        let frame = &buf[..buf.len() - unwritten];
+
+        let parsed_ip = Ipv4HeaderSlice::from_slice(frame).unwrap();
+        let header_len = parsed_ip.slice().len();
+        let parsed_tcph = TcpHeaderSlice::from_slice(&frame[header_len..]).unwrap();
+        let data = &frame[header_len + parsed_tcph.slice().len()..];
+
+        let parsed_ip = IpHeaderSlice::V4(parsed_ip);
+        assert_eq!(
+            parsed_ip.calc_tcp_checksum(&parsed_tcph, data),
+            Some(parsed_tcph.checksum())
+        );
+    }
+}